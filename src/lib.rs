@@ -1,5 +1,6 @@
 use std::mem;
 use std::borrow::Cow;
+use std::ops::{Bound, RangeBounds};
 
 
 enum PosType {
@@ -50,6 +51,10 @@ impl<'a> SearchKey<'a> {
 
 unsafe fn remove_entry<C>(node: &mut TrieNode<C>, levels: Vec<(*mut TrieNode<C>, usize)>) -> C {
     let data = node.data.take().unwrap();
+    node.count -= 1;
+    for &(l, _) in &levels {
+        (*l).count -= 1;
+    }
     if levels.is_empty() || !node.children.is_empty() {
         // Using node can't be deleted.
         return data;
@@ -70,6 +75,7 @@ pub struct NotFound<'a, C> {
     node: *mut TrieNode<C>,
     pos: PosType,
     left: SearchKey<'a>,
+    levels: Vec<(*mut TrieNode<C>, usize)>,
 }
 
 impl<'a, C> NotFound<'a, C> {
@@ -111,47 +117,58 @@ impl<'a, C> Found<'a, C> {
     }
 }
 
-// TODO: don't use recursion.
 #[inline]
-unsafe fn search_node<'a, C>(node: *mut TrieNode<C>, mut key: SearchKey<'a>, levels: usize) -> Result<Found<C>, NotFound<'a, C>> {
-    let n = &mut *node;
-    let prefix_size = common_prefix(&n.segment, key.as_ref());
-    key.consume(prefix_size);
-    if prefix_size != n.segment.len() {
-        return Err(NotFound {
-            node: node,
-            pos: PosType::Edge(prefix_size),
-            left: key,
-        });
-    }
-    if !key.is_empty() {
-        return match n.children.binary_search_by(|k| k.segment[0].cmp(&key.first())) {
-            Ok(i) => {
-                let mut e = search_node(&n.children[i] as *const _ as *mut _, key, levels + 1);
-                if let Ok(ref mut e) = e {
-                    e.levels.push((node, i));
-                }
-                e
-            },
-            Err(i) => Err(NotFound {
-                node: node,
-                pos: PosType::Child(i),
-                left: key,
-            })
-        };
-    }
-    if n.data.is_none() {
-        Err(NotFound {
-            node: node,
-            pos: PosType::Leaf,
-            left: SearchKey::borrow(&[]),
-        })
-    } else {
-        Ok(Found {
+unsafe fn search_node<'a, C>(root: *mut TrieNode<C>, key: SearchKey<'a>) -> Result<Found<C>, NotFound<'a, C>> {
+    let mut levels = Vec::new();
+    let result = search_node_descend(root, key, &mut levels);
+    // `levels` was built root-first as we descended; `remove_entry`'s
+    // prune loop (and the count fix-up in `VacantEntry`/`remove_entry`)
+    // expects it ordered from the closest ancestor outward to the root.
+    levels.reverse();
+    match result {
+        Ok((node, key)) => Ok(Found {
             node: node,
             key: key,
-            levels: Vec::with_capacity(levels),
-        })
+            levels: levels,
+        }),
+        Err((node, pos, left)) => Err(NotFound {
+            node: node,
+            pos: pos,
+            left: left,
+            levels: levels,
+        }),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+unsafe fn search_node_descend<'a, C>(
+    root: *mut TrieNode<C>,
+    mut key: SearchKey<'a>,
+    levels: &mut Vec<(*mut TrieNode<C>, usize)>,
+) -> Result<(*mut TrieNode<C>, SearchKey<'a>), (*mut TrieNode<C>, PosType, SearchKey<'a>)> {
+    let mut node = root;
+    loop {
+        let n = &mut *node;
+        let prefix_size = common_prefix(&n.segment, key.as_ref());
+        key.consume(prefix_size);
+        if prefix_size != n.segment.len() {
+            return Err((node, PosType::Edge(prefix_size), key));
+        }
+        if !key.is_empty() {
+            match n.children.binary_search_by(|k| k.segment[0].cmp(&key.first())) {
+                Ok(i) => {
+                    levels.push((node, i));
+                    node = &mut n.children[i] as *mut _;
+                    continue;
+                }
+                Err(i) => return Err((node, PosType::Child(i), key)),
+            }
+        }
+        if n.data.is_none() {
+            return Err((node, PosType::Leaf, SearchKey::borrow(&[])));
+        } else {
+            return Ok((node, key));
+        }
     }
 }
 
@@ -160,13 +177,24 @@ pub struct VacantEntry<'a, C: 'a> {
     pos: PosType,
     key: Vec<u8>,
     key_off: usize,
+    levels: Vec<(*mut TrieNode<C>, usize)>,
 }
 
 impl<'a, C> VacantEntry<'a, C> {
-    pub fn insert(self, val: C) -> &'a mut C {
+    fn bump_count(&mut self) {
+        self.node.count += 1;
+        for &(l, _) in &self.levels {
+            unsafe {
+                (*l).count += 1;
+            }
+        }
+    }
+
+    pub fn insert(mut self, val: C) -> &'a mut C {
         let pos = match self.pos {
             PosType::Edge(pos) => {
                 let mut split_child = TrieNode::new();
+                split_child.count = self.node.count;
                 mem::swap(&mut split_child.children, &mut self.node.children);
                 split_child.segment = self.node.segment[pos..].to_vec();
                 mem::swap(&mut split_child.data, &mut self.node.data);
@@ -174,6 +202,7 @@ impl<'a, C> VacantEntry<'a, C> {
                 self.node.segment.shrink_to_fit();
                 self.node.children.push(split_child);
                 if self.key.len() == self.key_off {
+                    self.bump_count();
                     self.node.data = Some(val);
                     return self.node.data.as_mut().unwrap();
                 }
@@ -185,11 +214,13 @@ impl<'a, C> VacantEntry<'a, C> {
             }
             PosType::Child(pos) => pos,
             PosType::Leaf => {
+                self.bump_count();
                 self.node.data = Some(val);
                 return self.node.data.as_mut().unwrap();
             }
         };
 
+        self.bump_count();
         let child = TrieNode {
             segment: if self.key_off == 0 {
                 self.key
@@ -198,6 +229,7 @@ impl<'a, C> VacantEntry<'a, C> {
             },
             children: vec![],
             data: Some(val),
+            count: 1,
         };
         self.node.children.insert(pos, child);
         self.node.children[pos].data.as_mut().unwrap()
@@ -297,24 +329,102 @@ pub struct TrieNode<C> {
     segment: Vec<u8>,
     children: Vec<TrieNode<C>>,
     data: Option<C>,
+    // Number of values stored in this node's subtree, including itself.
+    count: usize,
 }
 
 fn common_prefix(lhs: &[u8], rhs: &[u8]) -> usize {
     lhs.into_iter().zip(rhs).take_while(|&(l, r)| l == r).count()
 }
 
+fn subtree_count<C>(node: &TrieNode<C>) -> usize {
+    node.children.iter().map(|c| c.count).sum::<usize>() + node.data.is_some() as usize
+}
+
+/// Pops the innermost open node off `stack` and attaches it to its parent,
+/// now that no further key can extend it. Used by `from_sorted_iter`.
+fn close_top<C>(stack: &mut Vec<(TrieNode<C>, usize)>) {
+    let (mut node, _) = stack.pop().unwrap();
+    node.count = subtree_count(&node);
+    stack.last_mut().unwrap().0.children.push(node);
+}
+
 impl<C> TrieNode<C> {
     pub fn new() -> TrieNode<C> {
         TrieNode {
             segment: Vec::new(),
             children: Vec::new(),
             data: None,
+            count: 0,
+        }
+    }
+
+    /// Builds a trie from pairs given in ascending, unique key order.
+    ///
+    /// Each key only ever extends or closes out nodes already sitting on
+    /// `stack`, so every key is handled via one comparison against the
+    /// previous key instead of a fresh descent from the root. The input is
+    /// trusted to already be sorted; passing unsorted or duplicate keys
+    /// produces an unspecified (but not unsafe) trie.
+    pub fn from_sorted_iter<I>(iter: I) -> TrieNode<C>
+    where
+        I: IntoIterator<Item = (Vec<u8>, C)>,
+    {
+        // `stack` holds the currently open path from the root down to the
+        // node being extended by the key last processed, paired with the
+        // depth at which each node's `segment` begins. A node leaves the
+        // stack (and gets appended to its parent's `children`) as soon as
+        // we see a key whose shared prefix with the previous one proves no
+        // further key can ever land in its subtree.
+        let mut stack: Vec<(TrieNode<C>, usize)> = vec![(TrieNode::new(), 0)];
+        let mut prev_key: Vec<u8> = Vec::new();
+
+        for (key, value) in iter {
+            let cp = common_prefix(&prev_key, &key);
+
+            while stack.len() > 1 && cp <= stack.last().unwrap().1 {
+                close_top(&mut stack);
+            }
+
+            let top = stack.len() - 1;
+            let cur_end = stack[top].1 + stack[top].0.segment.len();
+            if cp < cur_end {
+                let offset = cp - stack[top].1;
+                let cur = &mut stack[top].0;
+                let mut tail = TrieNode::new();
+                tail.segment = cur.segment.split_off(offset);
+                mem::swap(&mut tail.children, &mut cur.children);
+                mem::swap(&mut tail.data, &mut cur.data);
+                tail.count = subtree_count(&tail);
+                cur.children.push(tail);
+            }
+
+            if cp == key.len() {
+                stack[top].0.data = Some(value);
+            } else {
+                let leaf = TrieNode {
+                    segment: key[cp..].to_vec(),
+                    children: Vec::new(),
+                    data: Some(value),
+                    count: 1,
+                };
+                stack.push((leaf, cp));
+            }
+
+            prev_key = key;
+        }
+
+        while stack.len() > 1 {
+            close_top(&mut stack);
         }
+        let mut root = stack.pop().unwrap().0;
+        root.count = subtree_count(&root);
+        root
     }
 
     pub fn entry(&mut self, key: Vec<u8>) -> Entry<C> {
         unsafe {
-            match search_node(self, SearchKey::new(key), 0) {
+            match search_node(self, SearchKey::new(key)) {
                 Ok(f) => {
                     Entry::Occupied(OccupiedEntry {
                         node: &mut *f.node,
@@ -328,6 +438,7 @@ impl<C> TrieNode<C> {
                         key: f.left.base.into_owned(),
                         pos: f.pos,
                         key_off: f.left.offset,
+                        levels: f.levels,
                     })
                 }
             }
@@ -344,14 +455,83 @@ impl<C> TrieNode<C> {
         }
     }
 
-    // TODO: cache len
     pub fn len(&self) -> usize {
-        self.children.iter().fold(0, |sum, n| sum + n.len()) + self.data.as_ref().map_or(0, |_| 1)
+        self.count
+    }
+
+    /// Returns the `n`-th key/value pair in ascending order, in O(depth).
+    pub fn select(&self, n: usize) -> Option<(Vec<u8>, &C)> {
+        if n >= self.count {
+            return None;
+        }
+        let mut node = self;
+        let mut idx = n;
+        let mut path = Vec::new();
+        loop {
+            path.extend_from_slice(&node.segment);
+            if let Some(d) = node.data.as_ref() {
+                if idx == 0 {
+                    return Some((path, d));
+                }
+                idx -= 1;
+            }
+            let mut next = None;
+            for child in &node.children {
+                if idx < child.count {
+                    next = Some(child);
+                    break;
+                }
+                idx -= child.count;
+            }
+            match next {
+                Some(child) => node = child,
+                // Unreachable as long as `count` is accurate.
+                None => return None,
+            }
+        }
+    }
+
+    /// Returns the number of stored keys that sort strictly before `key`, in
+    /// O(depth).
+    pub fn rank(&self, key: &[u8]) -> usize {
+        let mut node = self;
+        let mut rest = key;
+        let mut rank = 0;
+        loop {
+            let prefix_size = common_prefix(&node.segment, rest);
+            if prefix_size < node.segment.len() {
+                if prefix_size < rest.len() && node.segment[prefix_size] < rest[prefix_size] {
+                    rank += node.count;
+                }
+                return rank;
+            }
+            rest = &rest[prefix_size..];
+            if rest.is_empty() {
+                return rank;
+            }
+            if node.data.is_some() {
+                rank += 1;
+            }
+            match node.children.binary_search_by(|c| c.segment[0].cmp(&rest[0])) {
+                Ok(i) => {
+                    for child in &node.children[..i] {
+                        rank += child.count;
+                    }
+                    node = &node.children[i];
+                }
+                Err(i) => {
+                    for child in &node.children[..i] {
+                        rank += child.count;
+                    }
+                    return rank;
+                }
+            }
+        }
     }
 
     pub fn prefix_len(&self, prefix_key: &[u8]) -> usize {
         unsafe {
-            match search_node(self as *const _ as *mut TrieNode<C>, SearchKey::borrow(prefix_key), 0) {
+            match search_node(self as *const _ as *mut TrieNode<C>, SearchKey::borrow(prefix_key)) {
                 Ok(f) => f.len(),
                 Err(f) => f.prefix_len(),
             }
@@ -364,7 +544,7 @@ impl<C> TrieNode<C> {
 
     pub fn get(&self, key: &[u8]) -> Option<&C> {
         unsafe {
-            match search_node(self as *const _ as *mut _, SearchKey::borrow(key), 0) {
+            match search_node(self as *const _ as *mut _, SearchKey::borrow(key)) {
                 Ok(f) => {
                     let node = &mut *f.node;
                     node.data.as_ref()
@@ -376,7 +556,7 @@ impl<C> TrieNode<C> {
 
     pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut C> {
         unsafe {
-            match search_node(self, SearchKey::borrow(key), 0) {
+            match search_node(self, SearchKey::borrow(key)) {
                 Ok(f) => {
                     let node = &mut *f.node;
                     node.data.as_mut()
@@ -388,12 +568,712 @@ impl<C> TrieNode<C> {
 
     pub fn remove(&mut self, key: &[u8]) -> Option<C> {
         unsafe {
-            match search_node(self, SearchKey::borrow(key), 0) {
+            match search_node(self, SearchKey::borrow(key)) {
                 Ok(f) => Some(f.remove().1),
                 Err(_) => None,
             }
         }
     }
+
+    pub fn iter(&self) -> Iter<C> {
+        Iter::new(self)
+    }
+
+    pub fn keys(&self) -> Keys<C> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<C> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn find_longest_prefix(&self, key: &[u8]) -> Option<(Vec<u8>, &C)> {
+        let mut node = self;
+        let mut rest = key;
+        let mut path = Vec::new();
+        let mut best = None;
+        loop {
+            let prefix_size = common_prefix(&node.segment, rest);
+            path.extend_from_slice(&node.segment[..prefix_size]);
+            if prefix_size != node.segment.len() {
+                break;
+            }
+            rest = &rest[prefix_size..];
+            if let Some(ref d) = node.data {
+                best = Some((path.len(), d));
+            }
+            if rest.is_empty() {
+                break;
+            }
+            match node.children.binary_search_by(|c| c.segment[0].cmp(&rest[0])) {
+                Ok(i) => node = &node.children[i],
+                Err(_) => break,
+            }
+        }
+        best.map(|(len, d)| (path[..len].to_vec(), d))
+    }
+
+    pub fn find_prefixes(&self, key: &[u8]) -> Vec<(Vec<u8>, &C)> {
+        let mut node = self;
+        let mut rest = key;
+        let mut path = Vec::new();
+        let mut result = Vec::new();
+        loop {
+            let prefix_size = common_prefix(&node.segment, rest);
+            path.extend_from_slice(&node.segment[..prefix_size]);
+            if prefix_size != node.segment.len() {
+                break;
+            }
+            rest = &rest[prefix_size..];
+            if let Some(ref d) = node.data {
+                result.push((path.clone(), d));
+            }
+            if rest.is_empty() {
+                break;
+            }
+            match node.children.binary_search_by(|c| c.segment[0].cmp(&rest[0])) {
+                Ok(i) => node = &node.children[i],
+                Err(_) => break,
+            }
+        }
+        result
+    }
+
+    /// Iterates over every stored entry whose key starts with `prefix`, in
+    /// ascending order.
+    pub fn prefix_iter(&self, prefix: &[u8]) -> Iter<C> {
+        let mut node = self;
+        let mut rest = prefix;
+        let mut base = Vec::new();
+        loop {
+            let prefix_size = common_prefix(&node.segment, rest);
+            if prefix_size == rest.len() {
+                return Iter::with_base(node, base);
+            }
+            if prefix_size != node.segment.len() {
+                return Iter::empty();
+            }
+            rest = &rest[prefix_size..];
+            base.extend_from_slice(&node.segment);
+            match node.children.binary_search_by(|c| c.segment[0].cmp(&rest[0])) {
+                Ok(i) => node = &node.children[i],
+                Err(_) => return Iter::empty(),
+            }
+        }
+    }
+
+    /// Iterates over every stored entry whose key falls within `bounds`, in
+    /// ascending order.
+    pub fn range<R: RangeBounds<[u8]>>(&self, bounds: R) -> Range<C> {
+        let inner = match bounds.start_bound() {
+            Bound::Unbounded => self.iter(),
+            Bound::Included(key) => self.seek_lower(key, false),
+            Bound::Excluded(key) => self.seek_lower(key, true),
+        };
+        let end = match bounds.end_bound() {
+            Bound::Unbounded => EndBound::Unbounded,
+            Bound::Included(key) => EndBound::Included(key.to_vec()),
+            Bound::Excluded(key) => EndBound::Excluded(key.to_vec()),
+        };
+        Range {
+            inner: inner,
+            end: end,
+            done: false,
+        }
+    }
+
+    // Builds the in-order traversal stack positioned so that iteration
+    // resumes at `key` (inclusive unless `excluded`), reusing the same
+    // descent `search_node` uses: walk down consuming `common_prefix` at
+    // each node, skipping past children that sort before `key` via the
+    // `binary_search_by` children are already ordered by.
+    fn seek_lower(&self, key: &[u8], excluded: bool) -> Iter<C> {
+        let mut stack = Vec::new();
+        let mut path = Vec::new();
+        let mut node = self;
+        let mut rest = key;
+        loop {
+            let prefix_size = common_prefix(&node.segment, rest);
+            if prefix_size < node.segment.len() && prefix_size < rest.len()
+                && node.segment[prefix_size] < rest[prefix_size]
+            {
+                // `node` and its whole subtree sort strictly before `key`;
+                // the parent frame already points past it.
+                break;
+            }
+
+            let path_len = path.len();
+            let mut frame = Frame::new(node, path_len);
+
+            if prefix_size < node.segment.len() {
+                // `key` ends inside this node's segment or diverges from it at
+                // a smaller byte: everything in this subtree sorts after `key`.
+                stack.push(frame);
+                break;
+            }
+
+            rest = &rest[prefix_size..];
+            if rest.is_empty() {
+                // `key` lands exactly on this node.
+                frame.data_done = excluded || node.data.is_none();
+                stack.push(frame);
+                break;
+            }
+
+            match node.children.binary_search_by(|c| c.segment[0].cmp(&rest[0])) {
+                Ok(i) => {
+                    frame.data_done = true;
+                    frame.child_idx = i + 1;
+                    stack.push(frame);
+                    path.extend_from_slice(&node.segment);
+                    node = &node.children[i];
+                }
+                Err(i) => {
+                    frame.data_done = true;
+                    frame.child_idx = i;
+                    stack.push(frame);
+                    break;
+                }
+            }
+        }
+        Iter {
+            front: stack,
+            back: Vec::new(),
+            front_path: path,
+            back_path: Vec::new(),
+            remaining: self.len(),
+        }
+    }
+}
+
+enum EndBound {
+    Unbounded,
+    Included(Vec<u8>),
+    Excluded(Vec<u8>),
+}
+
+/// Iterator over entries whose keys fall within a [`RangeBounds`], in
+/// ascending order. Created by [`TrieNode::range`].
+pub struct Range<'a, C: 'a> {
+    inner: Iter<'a, C>,
+    end: EndBound,
+    done: bool,
+}
+
+impl<'a, C> Iterator for Range<'a, C> {
+    type Item = (Vec<u8>, &'a C);
+
+    fn next(&mut self) -> Option<(Vec<u8>, &'a C)> {
+        if self.done {
+            return None;
+        }
+        match self.inner.next() {
+            Some((key, value)) => {
+                let in_range = match self.end {
+                    EndBound::Unbounded => true,
+                    EndBound::Included(ref end) => key.as_slice() <= end.as_slice(),
+                    EndBound::Excluded(ref end) => key.as_slice() < end.as_slice(),
+                };
+                if in_range {
+                    Some((key, value))
+                } else {
+                    self.done = true;
+                    None
+                }
+            }
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+struct Frame<'a, C: 'a> {
+    node: &'a TrieNode<C>,
+    // Length of `path` before this node's own segment is appended.
+    path_len: usize,
+    // Length of `path` once this node's own segment has been appended; filled in
+    // when the frame is entered.
+    own_len: usize,
+    entered: bool,
+    // Forward traversal: index of the next child to descend into.
+    // Backward traversal: index one past the next child to descend into.
+    child_idx: usize,
+    // Whether this node's own `data` has already been considered for
+    // emission. Set by `range` when seeding a frame whose data must not be
+    // yielded, e.g. because it sorts before the requested range or is an
+    // excluded endpoint.
+    data_done: bool,
+}
+
+impl<'a, C> Frame<'a, C> {
+    fn new(node: &'a TrieNode<C>, path_len: usize) -> Frame<'a, C> {
+        Frame {
+            node: node,
+            path_len: path_len,
+            own_len: path_len,
+            entered: false,
+            child_idx: 0,
+            data_done: false,
+        }
+    }
+}
+
+/// In-order iterator over the keys and values stored in a subtree, yielding
+/// `(key, value)` pairs in ascending byte order. Both ends can be driven
+/// independently, which is what makes this a `DoubleEndedIterator`.
+pub struct Iter<'a, C: 'a> {
+    front: Vec<Frame<'a, C>>,
+    back: Vec<Frame<'a, C>>,
+    front_path: Vec<u8>,
+    back_path: Vec<u8>,
+    remaining: usize,
+}
+
+impl<'a, C> Iter<'a, C> {
+    fn new(root: &'a TrieNode<C>) -> Iter<'a, C> {
+        Iter::with_base(root, Vec::new())
+    }
+
+    // `base` is the path accumulated by the ancestors of `root`; `root`'s own
+    // segment is appended as the first frame is entered.
+    fn with_base(root: &'a TrieNode<C>, base: Vec<u8>) -> Iter<'a, C> {
+        let path_len = base.len();
+        Iter {
+            front: vec![Frame::new(root, path_len)],
+            back: vec![Frame::new(root, path_len)],
+            front_path: base.clone(),
+            back_path: base,
+            remaining: root.len(),
+        }
+    }
+
+    fn empty() -> Iter<'a, C> {
+        Iter {
+            front: Vec::new(),
+            back: Vec::new(),
+            front_path: Vec::new(),
+            back_path: Vec::new(),
+            remaining: 0,
+        }
+    }
+}
+
+impl<'a, C> Iterator for Iter<'a, C> {
+    type Item = (Vec<u8>, &'a C);
+
+    fn next(&mut self) -> Option<(Vec<u8>, &'a C)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let data = {
+                let frame = self.front.last_mut()?;
+                if !frame.entered {
+                    frame.entered = true;
+                    self.front_path.truncate(frame.path_len);
+                    self.front_path.extend_from_slice(&frame.node.segment);
+                    frame.own_len = self.front_path.len();
+                }
+                if !frame.data_done {
+                    frame.data_done = true;
+                    frame.node.data.as_ref()
+                } else {
+                    None
+                }
+            };
+            if let Some(d) = data {
+                self.remaining -= 1;
+                return Some((self.front_path.clone(), d));
+            }
+            let frame = self.front.last_mut().unwrap();
+            if frame.child_idx < frame.node.children.len() {
+                let child = &frame.node.children[frame.child_idx];
+                frame.child_idx += 1;
+                let path_len = frame.own_len;
+                self.front.push(Frame::new(child, path_len));
+            } else {
+                self.front.pop();
+            }
+        }
+    }
+}
+
+impl<'a, C> DoubleEndedIterator for Iter<'a, C> {
+    fn next_back(&mut self) -> Option<(Vec<u8>, &'a C)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let frame = self.back.last_mut()?;
+            if !frame.entered {
+                frame.entered = true;
+                self.back_path.truncate(frame.path_len);
+                self.back_path.extend_from_slice(&frame.node.segment);
+                frame.own_len = self.back_path.len();
+                frame.child_idx = frame.node.children.len();
+            }
+            if frame.child_idx > 0 {
+                frame.child_idx -= 1;
+                let child = &frame.node.children[frame.child_idx];
+                let path_len = frame.own_len;
+                self.back.push(Frame::new(child, path_len));
+                continue;
+            }
+            let frame = self.back.pop().unwrap();
+            self.back_path.truncate(frame.own_len);
+            if let Some(d) = frame.node.data.as_ref().filter(|_| !frame.data_done) {
+                self.remaining -= 1;
+                return Some((self.back_path.clone(), d));
+            }
+        }
+    }
+}
+
+pub struct Keys<'a, C: 'a> {
+    inner: Iter<'a, C>,
+}
+
+impl<'a, C> Iterator for Keys<'a, C> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        self.inner.next().map(|(k, _)| k)
+    }
+}
+
+impl<'a, C> DoubleEndedIterator for Keys<'a, C> {
+    fn next_back(&mut self) -> Option<Vec<u8>> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+pub struct Values<'a, C: 'a> {
+    inner: Iter<'a, C>,
+}
+
+impl<'a, C> Iterator for Values<'a, C> {
+    type Item = &'a C;
+
+    fn next(&mut self) -> Option<&'a C> {
+        self.inner.next().map(|(_, v)| v)
+    }
+}
+
+impl<'a, C> DoubleEndedIterator for Values<'a, C> {
+    fn next_back(&mut self) -> Option<&'a C> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+struct IntoFrame<C> {
+    segment: Vec<u8>,
+    data: Option<C>,
+    children: ::std::vec::IntoIter<TrieNode<C>>,
+    path_len: usize,
+    own_len: usize,
+    entered: bool,
+}
+
+impl<C> IntoFrame<C> {
+    fn new(node: TrieNode<C>, path_len: usize) -> IntoFrame<C> {
+        IntoFrame {
+            segment: node.segment,
+            data: node.data,
+            children: node.children.into_iter(),
+            path_len: path_len,
+            own_len: path_len,
+            entered: false,
+        }
+    }
+}
+
+/// Consuming in-order iterator, yielding `(key, value)` pairs in ascending
+/// byte order.
+pub struct IntoIter<C> {
+    stack: Vec<IntoFrame<C>>,
+    path: Vec<u8>,
+}
+
+impl<C> IntoIter<C> {
+    fn new(root: TrieNode<C>) -> IntoIter<C> {
+        IntoIter {
+            stack: vec![IntoFrame::new(root, 0)],
+            path: Vec::new(),
+        }
+    }
+}
+
+impl<C> Iterator for IntoIter<C> {
+    type Item = (Vec<u8>, C);
+
+    fn next(&mut self) -> Option<(Vec<u8>, C)> {
+        loop {
+            let data = {
+                let frame = self.stack.last_mut()?;
+                if !frame.entered {
+                    frame.entered = true;
+                    self.path.truncate(frame.path_len);
+                    self.path.extend_from_slice(&frame.segment);
+                    frame.own_len = self.path.len();
+                    frame.data.take()
+                } else {
+                    None
+                }
+            };
+            if let Some(d) = data {
+                return Some((self.path.clone(), d));
+            }
+            let frame = self.stack.last_mut().unwrap();
+            match frame.children.next() {
+                Some(child) => {
+                    let path_len = frame.own_len;
+                    self.stack.push(IntoFrame::new(child, path_len));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl<'a, C> IntoIterator for &'a TrieNode<C> {
+    type Item = (Vec<u8>, &'a C);
+    type IntoIter = Iter<'a, C>;
+
+    fn into_iter(self) -> Iter<'a, C> {
+        self.iter()
+    }
+}
+
+impl<C> IntoIterator for TrieNode<C> {
+    type Item = (Vec<u8>, C);
+    type IntoIter = IntoIter<C>;
+
+    fn into_iter(self) -> IntoIter<C> {
+        IntoIter::new(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::de::{self, Deserializer};
+    use serde::ser::Serializer;
+    use serde::{Deserialize, Serialize};
+
+    use super::{subtree_count, TrieNode};
+
+    // A node and its whole `children` subtree, flattened to pre-order so
+    // that serializing or deserializing never calls back into a
+    // `TrieNode<C>` (de)serializer for a child: doing that naively (e.g. via
+    // a derived `children: Vec<TrieNode<C>>` field) puts one serializer
+    // stack frame per trie level, which overflows on the same kind of deep,
+    // shared-prefix chains `search_node` was made iterative to handle.
+    #[derive(Serialize)]
+    struct FlatNodeRef<'a, C> {
+        segment: &'a [u8],
+        data: &'a Option<C>,
+        children: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct FlatNodeOwned<C> {
+        segment: Vec<u8>,
+        data: Option<C>,
+        children: usize,
+    }
+
+    impl<C: Serialize> Serialize for TrieNode<C> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut flat = Vec::new();
+            let mut stack = vec![self];
+            while let Some(node) = stack.pop() {
+                flat.push(FlatNodeRef {
+                    segment: &node.segment,
+                    data: &node.data,
+                    children: node.children.len(),
+                });
+                // Pushed in reverse so children come off the stack (and so
+                // appear in `flat`) in their original left-to-right order.
+                stack.extend(node.children.iter().rev());
+            }
+            flat.serialize(serializer)
+        }
+    }
+
+    impl<'de, C: Deserialize<'de>> Deserialize<'de> for TrieNode<C> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let flat = Vec::<FlatNodeOwned<C>>::deserialize(deserializer)?;
+            build_from_flat(flat).map_err(de::Error::custom)
+        }
+    }
+
+    // A node from `flat` whose `children` haven't all arrived yet.
+    struct Open<C> {
+        segment: Vec<u8>,
+        data: Option<C>,
+        children: Vec<TrieNode<C>>,
+        remaining: usize,
+    }
+
+    // Rebuilds the tree from a pre-order flattening by walking `flat` once
+    // and keeping the still-open ancestors of the node just read on `stack`;
+    // an entry with `remaining == 0` children is complete as soon as it's
+    // pushed, which folds it (and any ancestor it completes in turn) into
+    // its parent before the next entry is read.
+    fn build_from_flat<C>(flat: Vec<FlatNodeOwned<C>>) -> Result<TrieNode<C>, String> {
+        if flat.is_empty() {
+            return Err("trie data must contain at least the root node".to_string());
+        }
+        let mut stack: Vec<Open<C>> = Vec::new();
+        for entry in flat {
+            if stack.len() == 1 && stack[0].remaining == 0 {
+                return Err(
+                    "trie data has more nodes than the root's children account for".to_string(),
+                );
+            }
+            stack.push(Open {
+                segment: entry.segment,
+                data: entry.data,
+                children: Vec::with_capacity(entry.children),
+                remaining: entry.children,
+            });
+            while stack.len() > 1 && stack.last().unwrap().remaining == 0 {
+                let open = stack.pop().unwrap();
+                let node = close(open)?;
+                let parent = stack.last_mut().unwrap();
+                parent.children.push(node);
+                parent.remaining -= 1;
+            }
+        }
+        if stack.len() != 1 || stack[0].remaining != 0 {
+            return Err("trie data is truncated: a node is missing children".to_string());
+        }
+        close(stack.pop().unwrap())
+    }
+
+    // `search_node`'s `binary_search_by` assumes `children` is sorted by
+    // `segment[0]` with no two children sharing it; a trie loaded without
+    // that invariant would silently yield wrong lookups, so reject it here
+    // instead.
+    fn validate_children<C>(children: &[TrieNode<C>]) -> Result<(), String> {
+        let mut prev = None;
+        for child in children {
+            if child.segment.is_empty() {
+                return Err("trie child segment must not be empty".to_string());
+            }
+            let first = child.segment[0];
+            if prev.map_or(false, |p| first <= p) {
+                return Err(
+                    "trie children must be sorted by their first byte, with no two \
+                     children sharing it"
+                        .to_string(),
+                );
+            }
+            prev = Some(first);
+        }
+        Ok(())
+    }
+
+    fn close<C>(open: Open<C>) -> Result<TrieNode<C>, String> {
+        validate_children(&open.children)?;
+        let mut node = TrieNode {
+            segment: open.segment,
+            data: open.data,
+            children: open.children,
+            count: 0,
+        };
+        node.count = subtree_count(&node);
+        Ok(node)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::tests::build_trie;
+    use super::*;
+
+    #[test]
+    fn round_trip_preserves_len_and_order() {
+        let trie = build_trie();
+        let json = serde_json::to_string(&trie).unwrap();
+        let restored: TrieNode<usize> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(trie.len(), restored.len());
+        let original: Vec<_> = trie.iter().map(|(k, v)| (k, *v)).collect();
+        let round_tripped: Vec<_> = restored.iter().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(original, round_tripped);
+    }
+
+    #[test]
+    fn rejects_unsorted_children() {
+        let json = r#"[
+            {"segment": [], "data": null, "children": 2},
+            {"segment": [2], "data": 1, "children": 0},
+            {"segment": [1], "data": 0, "children": 0}
+        ]"#;
+        let err = match serde_json::from_str::<TrieNode<usize>>(json) {
+            Ok(_) => panic!("expected deserialize to reject unsorted children"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("sorted"));
+    }
+
+    #[test]
+    fn rejects_duplicate_first_byte_children() {
+        let json = r#"[
+            {"segment": [], "data": null, "children": 2},
+            {"segment": [1, 2], "data": 0, "children": 0},
+            {"segment": [1, 9], "data": 1, "children": 0}
+        ]"#;
+        let err = match serde_json::from_str::<TrieNode<usize>>(json) {
+            Ok(_) => panic!("expected deserialize to reject duplicate first-byte children"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("sorted"));
+    }
+
+    #[test]
+    fn rejects_empty_segment_child() {
+        let json = r#"[
+            {"segment": [], "data": null, "children": 1},
+            {"segment": [], "data": 0, "children": 0}
+        ]"#;
+        let err = match serde_json::from_str::<TrieNode<usize>>(json) {
+            Ok(_) => panic!("expected deserialize to reject an empty-segment child"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("empty"));
+    }
+
+    #[test]
+    fn round_trips_deep_chain_without_recursing() {
+        // Deep enough that a derive-style recursive (de)serializer would
+        // blow serde_json's default recursion limit (128) well before
+        // reaching this depth; matches the depth `test_deep_chain_does_not_overflow`
+        // already uses elsewhere in this file.
+        const DEPTH: usize = 10_000;
+        let pairs: Vec<(Vec<u8>, usize)> = (1..=DEPTH).map(|i| (vec![1u8; i], i)).collect();
+        let trie = TrieNode::from_sorted_iter(pairs);
+        let json = serde_json::to_string(&trie).unwrap();
+        let restored: TrieNode<usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(trie.len(), restored.len());
+    }
+
+    #[test]
+    fn rejects_extra_trailing_nodes() {
+        let json = r#"[
+            {"segment": [], "data": null, "children": 0},
+            {"segment": [1], "data": 0, "children": 0}
+        ]"#;
+        let err = match serde_json::from_str::<TrieNode<usize>>(json) {
+            Ok(_) => panic!("expected deserialize to reject trailing nodes past the root"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("more nodes"));
+    }
 }
 
 #[cfg(test)]
@@ -434,4 +1314,205 @@ mod tests {
             assert_eq!(len, trie.len());
         }
     }
+
+    pub(super) fn build_trie() -> TrieNode<usize> {
+        let mut trie = TrieNode::new();
+        let keys = vec![
+            vec![1, 2, 3],
+            vec![1, 2],
+            vec![1, 2, 3, 5],
+            vec![1, 2, 5, 3],
+            vec![2],
+            vec![],
+        ];
+        for (i, key) in keys.into_iter().enumerate() {
+            trie.insert(key, i);
+        }
+        trie
+    }
+
+    #[test]
+    fn test_iter() {
+        let trie = build_trie();
+        let sorted = vec![
+            (vec![], 5),
+            (vec![1, 2], 1),
+            (vec![1, 2, 3], 0),
+            (vec![1, 2, 3, 5], 2),
+            (vec![1, 2, 5, 3], 3),
+            (vec![2], 4),
+        ];
+
+        let collected: Vec<_> = trie.iter().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(sorted, collected);
+
+        let keys: Vec<_> = trie.keys().collect();
+        assert_eq!(
+            sorted.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>(),
+            keys
+        );
+
+        let values: Vec<_> = trie.values().cloned().collect();
+        assert_eq!(
+            sorted.iter().map(|(_, v)| *v).collect::<Vec<_>>(),
+            values
+        );
+
+        let mut rev_expected = sorted.clone();
+        rev_expected.reverse();
+        let rev: Vec<_> = trie.iter().rev().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(rev_expected, rev);
+
+        let into: Vec<_> = build_trie().into_iter().collect();
+        assert_eq!(sorted, into);
+    }
+
+    #[test]
+    fn test_prefix_apis() {
+        let trie = build_trie();
+
+        assert_eq!(
+            Some((vec![1, 2, 3, 5], &2)),
+            trie.find_longest_prefix(&[1, 2, 3, 5, 9])
+        );
+        assert_eq!(Some((vec![], &5)), trie.find_longest_prefix(&[9]));
+        assert_eq!(None::<(Vec<u8>, &usize)>, TrieNode::<usize>::new().find_longest_prefix(&[1]));
+
+        assert_eq!(
+            vec![
+                (vec![], &5),
+                (vec![1, 2], &1),
+                (vec![1, 2, 3], &0),
+                (vec![1, 2, 3, 5], &2),
+            ],
+            trie.find_prefixes(&[1, 2, 3, 5, 9])
+        );
+
+        let under_12: Vec<_> = trie.prefix_iter(&[1, 2]).map(|(k, v)| (k, *v)).collect();
+        assert_eq!(
+            vec![
+                (vec![1, 2], 1),
+                (vec![1, 2, 3], 0),
+                (vec![1, 2, 3, 5], 2),
+                (vec![1, 2, 5, 3], 3),
+            ],
+            under_12
+        );
+
+        assert_eq!(0, trie.prefix_iter(&[9]).count());
+    }
+
+    #[test]
+    fn test_select_and_rank() {
+        let trie = build_trie();
+        let sorted = vec![
+            (vec![], 5),
+            (vec![1, 2], 1),
+            (vec![1, 2, 3], 0),
+            (vec![1, 2, 3, 5], 2),
+            (vec![1, 2, 5, 3], 3),
+            (vec![2], 4),
+        ];
+
+        for (i, &(ref key, value)) in sorted.iter().enumerate() {
+            assert_eq!(Some((key.clone(), &value)), trie.select(i));
+            assert_eq!(i, trie.rank(key));
+        }
+        assert_eq!(None, trie.select(sorted.len()));
+        assert_eq!(sorted.len(), trie.rank(&[9]));
+        assert_eq!(3, trie.rank(&[1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_range() {
+        use std::ops::Bound;
+
+        let trie = build_trie();
+
+        let collect = |lo: Bound<&[u8]>, hi: Bound<&[u8]>| -> Vec<(Vec<u8>, usize)> {
+            trie.range((lo, hi)).map(|(k, v)| (k, *v)).collect()
+        };
+
+        assert_eq!(
+            vec![(vec![1, 2], 1), (vec![1, 2, 3], 0), (vec![1, 2, 3, 5], 2)],
+            collect(
+                Bound::Included(&[1, 2]),
+                Bound::Excluded(&[1, 2, 5, 3]),
+            )
+        );
+
+        assert_eq!(
+            vec![
+                (vec![1, 2, 3], 0),
+                (vec![1, 2, 3, 5], 2),
+                (vec![1, 2, 5, 3], 3),
+            ],
+            collect(Bound::Excluded(&[1, 2]), Bound::Excluded(&[2]))
+        );
+
+        assert_eq!(
+            vec![(vec![1, 2, 5, 3], 3), (vec![2], 4)],
+            collect(Bound::Included(&[1, 2, 4]), Bound::Unbounded)
+        );
+
+        assert_eq!(
+            vec![(vec![], 5), (vec![1, 2], 1)],
+            collect(Bound::Unbounded, Bound::Included(&[1, 2]))
+        );
+
+        assert_eq!(
+            Vec::<(Vec<u8>, usize)>::new(),
+            collect(Bound::Included(&[9]), Bound::Unbounded)
+        );
+    }
+
+    #[test]
+    fn test_from_sorted_iter() {
+        // Includes a key (`[1, 3]`) that diverges strictly inside the
+        // existing `[1, 2]` segment, exercising the mid-segment split path.
+        let pairs = vec![
+            (vec![], 5),
+            (vec![1, 2], 1),
+            (vec![1, 2, 3], 0),
+            (vec![1, 2, 3, 5], 2),
+            (vec![1, 2, 4], 6),
+            (vec![1, 2, 5, 3], 3),
+            (vec![1, 3], 7),
+            (vec![2], 4),
+        ];
+
+        let mut inserted = TrieNode::new();
+        for (key, value) in &pairs {
+            inserted.insert(key.clone(), *value);
+        }
+        let bulk = TrieNode::from_sorted_iter(pairs.clone());
+
+        assert_eq!(inserted.len(), bulk.len());
+        let expected: Vec<_> = inserted.iter().map(|(k, v)| (k, *v)).collect();
+        let actual: Vec<_> = bulk.iter().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(expected, actual);
+
+        for (key, value) in &pairs {
+            assert_eq!(Some(value), bulk.get(key));
+        }
+
+        assert_eq!(0, TrieNode::<usize>::from_sorted_iter(Vec::new()).len());
+    }
+
+    #[test]
+    fn test_deep_chain_does_not_overflow() {
+        // A key built one byte at a time drives one trie level per byte;
+        // `search_node` used to recurse per level, so this would have blown
+        // the stack before it became an explicit loop.
+        const DEPTH: usize = 10_000;
+        let mut trie = TrieNode::new();
+        for i in 1..=DEPTH {
+            trie.insert(vec![1u8; i], i);
+        }
+
+        assert_eq!(DEPTH, trie.len());
+        assert_eq!(Some(&DEPTH), trie.get(&vec![1u8; DEPTH]));
+        assert_eq!(Some(&(DEPTH / 2)), trie.get(&vec![1u8; DEPTH / 2]));
+        assert_eq!(None, trie.get(&vec![1u8; DEPTH + 1]));
+    }
 }